@@ -0,0 +1,116 @@
+// Copyright 2017 int08h LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! secp256k1 Schnorr (BIP340) `SignatureScheme`
+//!
+//! Implements `sign::SignatureScheme` with BIP340 Taproot Schnorr
+//! signatures over secp256k1, so a server can run with a secp256k1
+//! long-term key instead of Ed25519.
+
+extern crate k256;
+
+use self::k256::schnorr::signature::{Signer as _, Verifier as _};
+use self::k256::schnorr::{Signature, SigningKey, VerifyingKey};
+
+use sign::SignatureScheme;
+
+/// A secp256k1 BIP340 Schnorr `SignatureScheme`.
+pub struct Secp256k1SchnorrScheme {
+    signing_key: SigningKey,
+    public_key_bytes: Vec<u8>,
+}
+
+impl SignatureScheme for Secp256k1SchnorrScheme {
+    type PublicKey = Vec<u8>;
+
+    const SCHEME_ID: u32 = 2;
+
+    /// Domain-separation tag for this scheme, so a secp256k1-signed
+    /// message can never be replayed as a (context-free) Ed25519 one,
+    /// or vice versa.
+    fn context() -> &'static [u8] {
+        b"roughenough-secp256k1-schnorr-v1"
+    }
+
+    fn from_seed(seed: &[u8]) -> Self {
+        let signing_key = SigningKey::from_bytes(seed).expect("invalid secp256k1 seed");
+        let public_key_bytes = signing_key.verifying_key().to_bytes().to_vec();
+
+        Secp256k1SchnorrScheme {
+            signing_key,
+            public_key_bytes,
+        }
+    }
+
+    fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        self.signing_key.sign(msg).to_bytes().to_vec()
+    }
+
+    fn public_key_bytes(&self) -> &[u8] {
+        &self.public_key_bytes
+    }
+
+    fn decode_public_key(bytes: &[u8]) -> Self::PublicKey {
+        bytes.to_vec()
+    }
+
+    fn verify(pubkey: &Self::PublicKey, msg: &[u8], sig: &[u8]) -> bool {
+        let verifying_key = match VerifyingKey::from_bytes(pubkey) {
+            Ok(key) => key,
+            Err(_) => return false,
+        };
+        let signature = match Signature::try_from(sig) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+
+        verifying_key.verify(msg, &signature).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sign::{Signer as SchemeSigner, Verifier as SchemeVerifier};
+
+    #[test]
+    fn secp256k1_schnorr_sign_verify_round_trip() {
+        let seed = [3u8; 32];
+        let mut signer: SchemeSigner<Secp256k1SchnorrScheme> = SchemeSigner::new(&seed);
+        signer.update(b"Hello world");
+        let sig = signer.sign();
+
+        let mut verifier: SchemeVerifier<Secp256k1SchnorrScheme> =
+            SchemeVerifier::new(signer.public_key_bytes());
+        verifier.update(b"Hello world");
+
+        assert_eq!(verifier.verify(&sig), true);
+        assert_eq!(signer.scheme_id(), Secp256k1SchnorrScheme::SCHEME_ID);
+    }
+
+    #[test]
+    fn secp256k1_schnorr_rejects_tampered_signature() {
+        let seed = [4u8; 32];
+        let mut signer: SchemeSigner<Secp256k1SchnorrScheme> = SchemeSigner::new(&seed);
+        signer.update(b"Hello world");
+        let mut sig = signer.sign();
+        sig[0] ^= 0xff;
+
+        let mut verifier: SchemeVerifier<Secp256k1SchnorrScheme> =
+            SchemeVerifier::new(signer.public_key_bytes());
+        verifier.update(b"Hello world");
+
+        assert_eq!(verifier.verify(&sig), false);
+    }
+}
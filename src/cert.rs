@@ -0,0 +1,427 @@
+// Copyright 2017 int08h LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Time-bounded delegation of signing authority
+//!
+//! Roughtime keeps its long-term root key offline and has it sign a
+//! short-lived "online" key bounded by a validity window. Responses are
+//! signed by the online key, and a client checks both the delegation
+//! signature against the long-term key and that the response's
+//! timestamp falls inside the advertised `[min_timestamp, max_timestamp)`
+//! window.
+//!
+//! On the wire a `Certificate` uses Roughtime's own tag-value framing:
+//! a `u32` tag count, that many `u32` cumulative value offsets (for all
+//! but the first value), that many 4-byte tags in strictly ascending
+//! order, then the concatenated values. This mirrors how Roughtime
+//! messages are framed generally, scoped down to just the four fields
+//! a `Certificate` needs.
+
+use std::error::Error;
+use std::fmt;
+
+use sign::{Ed25519Scheme, Signer, Verifier};
+
+/// Domain-separation prefix for the bytes a `Certificate` signs, so a
+/// delegation signature can never be replayed as an ordinary response
+/// signature (or vice versa).
+const CERT_CONTEXT: &'static [u8] = b"RoughTime v1 delegation signature--\x00";
+
+const TAG_PUBK: [u8; 4] = *b"PUBK";
+const TAG_MINT: [u8; 4] = *b"MINT";
+const TAG_MAXT: [u8; 4] = *b"MAXT";
+const TAG_SIG: [u8; 4] = *b"SIG\0";
+
+/// Errors returned when decoding a `Certificate` from its wire form.
+#[derive(Debug)]
+pub enum CertificateError {
+    /// The tag-value framing itself was malformed, truncated, or had
+    /// tags out of the required ascending order.
+    Malformed,
+    /// A tag required by `Certificate` was missing from the message.
+    MissingTag([u8; 4]),
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CertificateError::Malformed => write!(f, "malformed certificate encoding"),
+            CertificateError::MissingTag(tag) => write!(f, "missing required tag {:?}", tag),
+        }
+    }
+}
+
+impl Error for CertificateError {}
+
+fn tag_u32(tag: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*tag)
+}
+
+/// Encode `fields` (tag, value) pairs using Roughtime's tag-value
+/// framing, sorting by tag along the way.
+fn encode_tags(fields: &[([u8; 4], &[u8])]) -> Vec<u8> {
+    let mut sorted: Vec<([u8; 4], &[u8])> = fields.to_vec();
+    sorted.sort_by_key(|(tag, _)| tag_u32(tag));
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&(sorted.len() as u32).to_le_bytes());
+
+    let mut offset = 0u32;
+    for &(_, value) in sorted.iter().take(sorted.len().saturating_sub(1)) {
+        offset += value.len() as u32;
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    for &(tag, _) in &sorted {
+        out.extend_from_slice(&tag);
+    }
+
+    for &(_, value) in &sorted {
+        out.extend_from_slice(value);
+    }
+
+    out
+}
+
+/// Decode Roughtime tag-value framing into its (tag, value) pairs.
+/// Rejects truncated framing and tags that aren't in strictly
+/// ascending order (the only canonical encoding).
+fn decode_tags(bytes: &[u8]) -> Result<Vec<([u8; 4], Vec<u8>)>, CertificateError> {
+    if bytes.len() < 4 {
+        return Err(CertificateError::Malformed);
+    }
+
+    let mut num_tags_bytes = [0u8; 4];
+    num_tags_bytes.copy_from_slice(&bytes[..4]);
+    let num_tags = u32::from_le_bytes(num_tags_bytes) as usize;
+
+    if num_tags == 0 {
+        return Ok(Vec::new());
+    }
+
+    let header_len = 4 + 4 * (num_tags - 1);
+    let tags_start = header_len;
+    let values_start = tags_start + 4 * num_tags;
+
+    if bytes.len() < values_start {
+        return Err(CertificateError::Malformed);
+    }
+
+    let mut offsets = Vec::with_capacity(num_tags);
+    offsets.push(0u32);
+    for i in 0..num_tags - 1 {
+        let start = 4 + 4 * i;
+        let mut offset_bytes = [0u8; 4];
+        offset_bytes.copy_from_slice(&bytes[start..start + 4]);
+        let offset = u32::from_le_bytes(offset_bytes);
+
+        if offset < *offsets.last().unwrap() {
+            return Err(CertificateError::Malformed);
+        }
+        offsets.push(offset);
+    }
+
+    let values = &bytes[values_start..];
+    if *offsets.last().unwrap() as usize > values.len() {
+        return Err(CertificateError::Malformed);
+    }
+
+    let mut fields = Vec::with_capacity(num_tags);
+    let mut prev_tag: Option<u32> = None;
+
+    for i in 0..num_tags {
+        let tag_offset = tags_start + 4 * i;
+        let mut tag = [0u8; 4];
+        tag.copy_from_slice(&bytes[tag_offset..tag_offset + 4]);
+
+        let tag_val = tag_u32(&tag);
+        if prev_tag.map_or(false, |prev| tag_val <= prev) {
+            return Err(CertificateError::Malformed);
+        }
+        prev_tag = Some(tag_val);
+
+        let start = offsets[i] as usize;
+        let end = if i + 1 < num_tags {
+            offsets[i + 1] as usize
+        } else {
+            values.len()
+        };
+
+        if end < start || end > values.len() {
+            return Err(CertificateError::Malformed);
+        }
+
+        fields.push((tag, values[start..end].to_vec()));
+    }
+
+    Ok(fields)
+}
+
+fn find_tag(fields: &[([u8; 4], Vec<u8>)], tag: [u8; 4]) -> Result<Vec<u8>, CertificateError> {
+    fields
+        .iter()
+        .find(|(t, _)| *t == tag)
+        .map(|(_, v)| v.clone())
+        .ok_or(CertificateError::MissingTag(tag))
+}
+
+/// A delegated public key together with the `[min_timestamp,
+/// max_timestamp)` window (microseconds since the epoch) the long-term
+/// key vouches for it, and the signature binding the two together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Certificate {
+    delegated_pubkey: Vec<u8>,
+    min_timestamp: u64,
+    max_timestamp: u64,
+    signature: Vec<u8>,
+}
+
+impl Certificate {
+    /// Bytes signed by the long-term key: context || min || max || pubkey
+    fn signed_bytes(delegated_pubkey: &[u8], min_timestamp: u64, max_timestamp: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(CERT_CONTEXT.len() + 16 + delegated_pubkey.len());
+        buf.extend_from_slice(CERT_CONTEXT);
+        buf.extend_from_slice(&min_timestamp.to_le_bytes());
+        buf.extend_from_slice(&max_timestamp.to_le_bytes());
+        buf.extend_from_slice(delegated_pubkey);
+        buf
+    }
+
+    pub fn delegated_pubkey(&self) -> &[u8] {
+        &self.delegated_pubkey
+    }
+
+    pub fn min_timestamp(&self) -> u64 {
+        self.min_timestamp
+    }
+
+    pub fn max_timestamp(&self) -> u64 {
+        self.max_timestamp
+    }
+
+    /// Does `timestamp` (microseconds since the epoch) fall inside this
+    /// certificate's validity window?
+    pub fn covers(&self, timestamp: u64) -> bool {
+        timestamp >= self.min_timestamp && timestamp < self.max_timestamp
+    }
+
+    /// Verify this certificate's signature against the long-term
+    /// (offline) key claimed to have issued it.
+    pub fn verify(&self, root_pubkey: &[u8]) -> bool {
+        let msg = Self::signed_bytes(&self.delegated_pubkey, self.min_timestamp, self.max_timestamp);
+
+        let mut v = Verifier::<Ed25519Scheme>::new(root_pubkey);
+        v.update(&msg);
+        v.verify(&self.signature)
+    }
+
+    /// Verify this certificate against `root_pubkey` and check that
+    /// `timestamp` falls inside its validity window. Both must hold for
+    /// the delegation to be accepted.
+    pub fn verify_for_timestamp(&self, root_pubkey: &[u8], timestamp: u64) -> bool {
+        self.verify(root_pubkey) && self.covers(timestamp)
+    }
+
+    /// Encode this certificate using Roughtime's tag-value wire
+    /// framing, so it can be transmitted alongside a response instead
+    /// of only existing in memory.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let min_bytes = self.min_timestamp.to_le_bytes();
+        let max_bytes = self.max_timestamp.to_le_bytes();
+
+        encode_tags(&[
+            (TAG_PUBK, &self.delegated_pubkey),
+            (TAG_MINT, &min_bytes),
+            (TAG_MAXT, &max_bytes),
+            (TAG_SIG, &self.signature),
+        ])
+    }
+
+    /// Decode a certificate previously produced by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CertificateError> {
+        let fields = decode_tags(bytes)?;
+
+        let delegated_pubkey = find_tag(&fields, TAG_PUBK)?;
+        let min_bytes = find_tag(&fields, TAG_MINT)?;
+        let max_bytes = find_tag(&fields, TAG_MAXT)?;
+        let signature = find_tag(&fields, TAG_SIG)?;
+
+        if min_bytes.len() != 8 || max_bytes.len() != 8 {
+            return Err(CertificateError::Malformed);
+        }
+
+        let mut min_arr = [0u8; 8];
+        min_arr.copy_from_slice(&min_bytes);
+        let mut max_arr = [0u8; 8];
+        max_arr.copy_from_slice(&max_bytes);
+
+        Ok(Certificate {
+            delegated_pubkey,
+            min_timestamp: u64::from_le_bytes(min_arr),
+            max_timestamp: u64::from_le_bytes(max_arr),
+            signature,
+        })
+    }
+}
+
+/// Builds a `Certificate` by signing a delegated key's validity window
+/// with the offline long-term `Signer`.
+pub struct CertificateBuilder<'a> {
+    signer: &'a mut Signer,
+    delegated_pubkey: Vec<u8>,
+    min_timestamp: Option<u64>,
+    max_timestamp: Option<u64>,
+}
+
+impl<'a> CertificateBuilder<'a> {
+    pub fn new(signer: &'a mut Signer, delegated_pubkey: &[u8]) -> Self {
+        CertificateBuilder {
+            signer,
+            delegated_pubkey: delegated_pubkey.to_vec(),
+            min_timestamp: None,
+            max_timestamp: None,
+        }
+    }
+
+    /// Set the start of the validity window (inclusive).
+    pub fn not_before(mut self, min_timestamp: u64) -> Self {
+        self.min_timestamp = Some(min_timestamp);
+        self
+    }
+
+    /// Set the end of the validity window (exclusive).
+    pub fn not_after(mut self, max_timestamp: u64) -> Self {
+        self.max_timestamp = Some(max_timestamp);
+        self
+    }
+
+    /// Sign the delegated key and its validity window, producing a
+    /// `Certificate`.
+    ///
+    /// Panics if `not_before`/`not_after` were not set, or if the
+    /// resulting window is empty (`min_timestamp >= max_timestamp`).
+    pub fn build(self) -> Certificate {
+        let min_timestamp = self.min_timestamp.expect("not_before must be set");
+        let max_timestamp = self.max_timestamp.expect("not_after must be set");
+        assert!(min_timestamp < max_timestamp, "certificate validity window is empty");
+
+        let msg = Certificate::signed_bytes(&self.delegated_pubkey, min_timestamp, max_timestamp);
+        self.signer.update(&msg);
+        let signature = self.signer.sign();
+
+        Certificate {
+            delegated_pubkey: self.delegated_pubkey,
+            min_timestamp,
+            max_timestamp,
+            signature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn delegated_certificate_verifies_and_covers_timestamp() {
+        let root_seed = [7u8; 32];
+        let mut root_signer = Signer::<Ed25519Scheme>::new(&root_seed);
+        let root_pubkey = root_signer.public_key_bytes().to_vec();
+
+        let online_seed = [9u8; 32];
+        let online_signer = Signer::<Ed25519Scheme>::new(&online_seed);
+        let online_pubkey = online_signer.public_key_bytes().to_vec();
+
+        let cert = CertificateBuilder::new(&mut root_signer, &online_pubkey)
+            .not_before(1_000)
+            .not_after(2_000)
+            .build();
+
+        assert_eq!(cert.verify(&root_pubkey), true);
+        assert_eq!(cert.verify_for_timestamp(&root_pubkey, 1_500), true);
+    }
+
+    #[test]
+    fn certificate_rejects_timestamp_outside_window() {
+        let root_seed = [7u8; 32];
+        let mut root_signer = Signer::<Ed25519Scheme>::new(&root_seed);
+        let root_pubkey = root_signer.public_key_bytes().to_vec();
+
+        let online_seed = [9u8; 32];
+        let online_signer = Signer::<Ed25519Scheme>::new(&online_seed);
+        let online_pubkey = online_signer.public_key_bytes().to_vec();
+
+        let cert = CertificateBuilder::new(&mut root_signer, &online_pubkey)
+            .not_before(1_000)
+            .not_after(2_000)
+            .build();
+
+        assert_eq!(cert.verify_for_timestamp(&root_pubkey, 2_000), false);
+        assert_eq!(cert.verify_for_timestamp(&root_pubkey, 999), false);
+    }
+
+    #[test]
+    fn certificate_rejects_wrong_root_key() {
+        let root_seed = [7u8; 32];
+        let mut root_signer = Signer::<Ed25519Scheme>::new(&root_seed);
+
+        let wrong_root_seed = [8u8; 32];
+        let wrong_root_signer = Signer::<Ed25519Scheme>::new(&wrong_root_seed);
+        let wrong_root_pubkey = wrong_root_signer.public_key_bytes().to_vec();
+
+        let online_seed = [9u8; 32];
+        let online_signer = Signer::<Ed25519Scheme>::new(&online_seed);
+        let online_pubkey = online_signer.public_key_bytes().to_vec();
+
+        let cert = CertificateBuilder::new(&mut root_signer, &online_pubkey)
+            .not_before(1_000)
+            .not_after(2_000)
+            .build();
+
+        assert_eq!(cert.verify(&wrong_root_pubkey), false);
+    }
+
+    #[test]
+    fn certificate_wire_round_trips() {
+        let root_seed = [7u8; 32];
+        let mut root_signer = Signer::<Ed25519Scheme>::new(&root_seed);
+
+        let online_seed = [9u8; 32];
+        let online_signer = Signer::<Ed25519Scheme>::new(&online_seed);
+        let online_pubkey = online_signer.public_key_bytes().to_vec();
+
+        let cert = CertificateBuilder::new(&mut root_signer, &online_pubkey)
+            .not_before(1_000)
+            .not_after(2_000)
+            .build();
+
+        let decoded = Certificate::from_bytes(&cert.to_bytes()).unwrap();
+        assert_eq!(decoded, cert);
+    }
+
+    #[test]
+    fn certificate_from_bytes_rejects_truncated_input() {
+        let result = Certificate::from_bytes(&[1, 0, 0, 0]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn certificate_from_bytes_rejects_missing_tag() {
+        // A well-formed single-tag message, but not one of the tags a
+        // `Certificate` requires.
+        let encoded = encode_tags(&[(*b"XXXX", &[1, 2, 3, 4])]);
+        let result = Certificate::from_bytes(&encoded);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2017 int08h LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! FROST threshold Ed25519 signing
+//!
+//! Lets `t` of `n` participants jointly produce an ordinary Ed25519
+//! signature, verifiable by the existing `sign::Verifier`, without any
+//! single machine ever holding the long-term key. Key generation
+//! Shamir-shares a random scalar `s` among `n` participants (trusted
+//! dealer); signing is two rounds: round one has each participant
+//! publish nonce commitments `(D_i, E_i)`, round two has a
+//! `Coordinator` bind them into a signing package that each
+//! `ThresholdSigner` uses to produce a share `z_i`, which the
+//! coordinator then sums into the final `(R, z)` signature.
+
+extern crate curve25519_dalek;
+extern crate rand;
+extern crate sha2;
+
+use std::error::Error;
+use std::fmt;
+
+use self::curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use self::curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use self::curve25519_dalek::scalar::Scalar;
+use self::curve25519_dalek::traits::Identity;
+use self::sha2::{Digest, Sha512};
+
+/// Errors returned by the coordinator side of a FROST signing session.
+#[derive(Debug)]
+pub enum FrostError {
+    /// `group_public_key` was not a canonically-encoded Ed25519 point.
+    InvalidGroupPublicKey,
+}
+
+impl fmt::Display for FrostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrostError::InvalidGroupPublicKey => write!(f, "invalid group public key"),
+        }
+    }
+}
+
+impl Error for FrostError {}
+
+/// Overwrite `scalars` with zero so the trusted dealer's coefficients
+/// (and thus the shared secret `s`) don't linger in memory any longer
+/// than necessary. The fence discourages, though does not formally
+/// guarantee, the compiler optimizing away the "dead" store.
+fn zeroize_scalars(scalars: &mut [Scalar]) {
+    for s in scalars.iter_mut() {
+        *s = Scalar::zero();
+    }
+    ::std::sync::atomic::compiler_fence(::std::sync::atomic::Ordering::SeqCst);
+}
+
+/// One participant's share of the group signing key, plus the group's
+/// public key. Dropping a `KeyShare` zeroes its secret scalar. Cloning
+/// one (e.g. to hand it to a `ThresholdSigner`) copies the secret; each
+/// clone's copy stays in memory until that clone is dropped.
+#[derive(Clone)]
+pub struct KeyShare {
+    pub index: u16,
+    secret: Scalar,
+    pub group_public_key: Vec<u8>,
+}
+
+impl Drop for KeyShare {
+    fn drop(&mut self) {
+        self.secret = Scalar::zero();
+        ::std::sync::atomic::compiler_fence(::std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// Shamir-share a freshly generated scalar `s` among `participants`
+/// participants so that any `threshold` of them can sign, publishing
+/// the group public key `A = [s]*B`. This is a trusted-dealer DKG, not
+/// a substitute for a real distributed key generation protocol: for
+/// the brief window between generating `s` and zeroizing the
+/// coefficients below, this one process holds the entire group secret.
+pub fn generate_shares(threshold: usize, participants: usize) -> Vec<KeyShare> {
+    assert!(threshold >= 1 && threshold <= participants, "invalid threshold");
+
+    let mut rng = rand::thread_rng();
+    let mut coefficients: Vec<Scalar> = (0..threshold).map(|_| Scalar::random(&mut rng)).collect();
+    let group_public_key = (&coefficients[0] * &ED25519_BASEPOINT_TABLE).compress().to_bytes().to_vec();
+
+    let shares = (1..=participants)
+        .map(|i| {
+            let x = Scalar::from(i as u64);
+            let mut secret = Scalar::zero();
+            let mut x_pow = Scalar::one();
+            for c in &coefficients {
+                secret += c * x_pow;
+                x_pow *= x;
+            }
+
+            KeyShare {
+                index: i as u16,
+                secret,
+                group_public_key: group_public_key.clone(),
+            }
+        })
+        .collect();
+
+    zeroize_scalars(&mut coefficients);
+    shares
+}
+
+/// The Lagrange coefficient `lambda_i` for participant `index` over the
+/// active signer set `signer_indices`.
+fn lagrange_coefficient(index: u16, signer_indices: &[u16]) -> Scalar {
+    let xi = Scalar::from(index as u64);
+    let mut numerator = Scalar::one();
+    let mut denominator = Scalar::one();
+
+    for &j in signer_indices {
+        if j == index {
+            continue;
+        }
+        let xj = Scalar::from(j as u64);
+        numerator *= xj;
+        denominator *= xj - xi;
+    }
+
+    numerator * denominator.invert()
+}
+
+/// A participant's private round-one nonce pair `(d_i, e_i)`, kept
+/// secret until round two.
+pub struct NonceSecret {
+    d: Scalar,
+    e: Scalar,
+}
+
+/// A participant's public round-one commitments `(D_i, E_i)`.
+#[derive(Clone)]
+pub struct NonceCommitment {
+    pub index: u16,
+    d_point: EdwardsPoint,
+    e_point: EdwardsPoint,
+}
+
+/// A FROST signing participant holding one key share.
+pub struct ThresholdSigner {
+    share: KeyShare,
+}
+
+impl ThresholdSigner {
+    pub fn new(share: KeyShare) -> Self {
+        ThresholdSigner { share }
+    }
+
+    pub fn index(&self) -> u16 {
+        self.share.index
+    }
+
+    /// Round one: sample a fresh nonce pair and publish its commitment.
+    pub fn commit(&self) -> (NonceSecret, NonceCommitment) {
+        let mut rng = rand::thread_rng();
+        let d = Scalar::random(&mut rng);
+        let e = Scalar::random(&mut rng);
+
+        let commitment = NonceCommitment {
+            index: self.share.index,
+            d_point: &d * &ED25519_BASEPOINT_TABLE,
+            e_point: &e * &ED25519_BASEPOINT_TABLE,
+        };
+
+        (NonceSecret { d, e }, commitment)
+    }
+
+    /// Round two: given this participant's round-one nonce and the
+    /// coordinator's `SigningPackage`, produce this participant's
+    /// signature share `z_i`.
+    pub fn sign(&self, nonce: &NonceSecret, package: &SigningPackage) -> Scalar {
+        let rho_i = package.binding_factor(self.share.index);
+        let lambda_i = lagrange_coefficient(self.share.index, &package.signer_indices());
+
+        nonce.d + nonce.e * rho_i + lambda_i * self.share.secret * package.challenge
+    }
+}
+
+/// Binds a set of round-one commitments to a message: the per-signer
+/// binding factors `rho_i`, the group nonce `R`, and the Ed25519
+/// challenge `c = SHA512(R || A || m) mod L`.
+pub struct SigningPackage {
+    message: Vec<u8>,
+    commitments: Vec<NonceCommitment>,
+    group_nonce: EdwardsPoint,
+    challenge: Scalar,
+}
+
+impl SigningPackage {
+    fn binding_factor(&self, index: u16) -> Scalar {
+        let mut hasher = Sha512::new();
+        hasher.update(index.to_le_bytes());
+        hasher.update(&self.message);
+        for c in &self.commitments {
+            hasher.update(c.d_point.compress().as_bytes());
+            hasher.update(c.e_point.compress().as_bytes());
+        }
+        Scalar::from_hash(hasher)
+    }
+
+    fn signer_indices(&self) -> Vec<u16> {
+        self.commitments.iter().map(|c| c.index).collect()
+    }
+}
+
+/// The coordinator of a FROST signing session: collects round-one
+/// commitments into a `SigningPackage`, then aggregates round-two
+/// shares into the final Ed25519 signature.
+pub struct Coordinator;
+
+impl Coordinator {
+    /// Build the round-two `SigningPackage` from the active signers'
+    /// round-one commitments. Returns an error rather than panicking if
+    /// `group_public_key` is malformed or not a canonical point encoding.
+    pub fn build_signing_package(
+        group_public_key: &[u8],
+        message: &[u8],
+        commitments: Vec<NonceCommitment>,
+    ) -> Result<SigningPackage, FrostError> {
+        if group_public_key.len() != 32 {
+            return Err(FrostError::InvalidGroupPublicKey);
+        }
+
+        let group_point = match CompressedEdwardsY::from_slice(group_public_key).decompress() {
+            Some(point) if point.compress().as_bytes() == group_public_key => point,
+            _ => return Err(FrostError::InvalidGroupPublicKey),
+        };
+
+        let mut package = SigningPackage {
+            message: message.to_vec(),
+            commitments,
+            group_nonce: EdwardsPoint::identity(),
+            challenge: Scalar::zero(),
+        };
+
+        package.group_nonce = package.commitments.iter().fold(EdwardsPoint::identity(), |acc, c| {
+            let rho_i = package.binding_factor(c.index);
+            acc + c.d_point + rho_i * c.e_point
+        });
+
+        let mut hasher = Sha512::new();
+        hasher.update(package.group_nonce.compress().as_bytes());
+        hasher.update(group_point.compress().as_bytes());
+        hasher.update(&package.message);
+        package.challenge = Scalar::from_hash(hasher);
+
+        Ok(package)
+    }
+
+    /// Sum each active signer's `z_i` and assemble the final `(R, z)`
+    /// Ed25519 signature, droppable straight into the existing
+    /// response-signing path.
+    pub fn aggregate(package: &SigningPackage, shares: &[Scalar]) -> Vec<u8> {
+        let z = shares.iter().fold(Scalar::zero(), |acc, s| acc + s);
+
+        let mut signature = Vec::with_capacity(64);
+        signature.extend_from_slice(package.group_nonce.compress().as_bytes());
+        signature.extend_from_slice(z.as_bytes());
+        signature
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sign::{Ed25519Scheme, Verifier};
+
+    #[test]
+    fn two_of_three_threshold_signature_verifies() {
+        let shares = generate_shares(2, 3);
+        let group_public_key = shares[0].group_public_key.clone();
+
+        let active: Vec<&KeyShare> = vec![&shares[0], &shares[2]];
+        let signers: Vec<ThresholdSigner> = active
+            .iter()
+            .map(|share| ThresholdSigner::new((*share).clone()))
+            .collect();
+
+        let message = b"Hello world";
+
+        let round_one: Vec<(NonceSecret, NonceCommitment)> =
+            signers.iter().map(|s| s.commit()).collect();
+        let commitments: Vec<NonceCommitment> =
+            round_one.iter().map(|(_, c)| c.clone()).collect();
+
+        let package = Coordinator::build_signing_package(&group_public_key, message, commitments).unwrap();
+
+        let z_shares: Vec<Scalar> = signers
+            .iter()
+            .zip(round_one.iter())
+            .map(|(signer, (nonce, _))| signer.sign(nonce, &package))
+            .collect();
+
+        let signature = Coordinator::aggregate(&package, &z_shares);
+
+        let mut v = Verifier::<Ed25519Scheme>::new(&group_public_key);
+        v.update(message);
+        assert_eq!(v.verify(&signature), true);
+    }
+
+    #[test]
+    fn tampered_signature_share_fails_to_verify() {
+        let shares = generate_shares(2, 3);
+        let group_public_key = shares[0].group_public_key.clone();
+
+        let active: Vec<&KeyShare> = vec![&shares[0], &shares[2]];
+        let signers: Vec<ThresholdSigner> = active
+            .iter()
+            .map(|share| ThresholdSigner::new((*share).clone()))
+            .collect();
+
+        let message = b"Hello world";
+
+        let round_one: Vec<(NonceSecret, NonceCommitment)> =
+            signers.iter().map(|s| s.commit()).collect();
+        let commitments: Vec<NonceCommitment> =
+            round_one.iter().map(|(_, c)| c.clone()).collect();
+
+        let package = Coordinator::build_signing_package(&group_public_key, message, commitments).unwrap();
+
+        let mut z_shares: Vec<Scalar> = signers
+            .iter()
+            .zip(round_one.iter())
+            .map(|(signer, (nonce, _))| signer.sign(nonce, &package))
+            .collect();
+
+        // Corrupt one signer's share, as if it were malicious or buggy.
+        z_shares[0] += Scalar::one();
+
+        let signature = Coordinator::aggregate(&package, &z_shares);
+
+        let mut v = Verifier::<Ed25519Scheme>::new(&group_public_key);
+        v.update(message);
+        assert_eq!(v.verify(&signature), false);
+    }
+
+    #[test]
+    fn below_threshold_signer_set_fails_to_verify() {
+        let shares = generate_shares(2, 3);
+        let group_public_key = shares[0].group_public_key.clone();
+
+        // Only one signer out of a threshold of two: the Lagrange
+        // coefficients computed over this (too-small) signer set do
+        // not reconstruct the group secret.
+        let active: Vec<&KeyShare> = vec![&shares[0]];
+        let signers: Vec<ThresholdSigner> = active
+            .iter()
+            .map(|share| ThresholdSigner::new((*share).clone()))
+            .collect();
+
+        let message = b"Hello world";
+
+        let round_one: Vec<(NonceSecret, NonceCommitment)> =
+            signers.iter().map(|s| s.commit()).collect();
+        let commitments: Vec<NonceCommitment> =
+            round_one.iter().map(|(_, c)| c.clone()).collect();
+
+        let package = Coordinator::build_signing_package(&group_public_key, message, commitments).unwrap();
+
+        let z_shares: Vec<Scalar> = signers
+            .iter()
+            .zip(round_one.iter())
+            .map(|(signer, (nonce, _))| signer.sign(nonce, &package))
+            .collect();
+
+        let signature = Coordinator::aggregate(&package, &z_shares);
+
+        let mut v = Verifier::<Ed25519Scheme>::new(&group_public_key);
+        v.update(message);
+        assert_eq!(v.verify(&signature), false);
+    }
+}
@@ -0,0 +1,260 @@
+// Copyright 2017 int08h LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Key generation, serialization, and on-disk persistence
+//!
+//! `Signer::new` only accepts a caller-supplied seed, leaving key
+//! creation and storage to the operator. `Keypair` generates a fresh
+//! seed from a CSPRNG, serializes it to raw bytes or base58, and
+//! reads/writes it to a file with permissions restricted to the owner.
+
+extern crate bs58;
+extern crate rand;
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use self::rand::RngCore;
+
+use sign::{Ed25519Scheme, Signer};
+
+const SEED_LEN: usize = 32;
+
+/// Errors returned when creating, decoding, or persisting a `Keypair`.
+#[derive(Debug)]
+pub enum KeypairError {
+    /// Key material was not exactly `SEED_LEN` bytes.
+    InvalidLength(usize),
+    /// Base58 decoding failed.
+    InvalidBase58,
+    /// An I/O error occurred while reading or writing a key file.
+    Io(::std::io::Error),
+}
+
+impl fmt::Display for KeypairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeypairError::InvalidLength(len) => {
+                write!(f, "expected a {}-byte seed, got {} bytes", SEED_LEN, len)
+            }
+            KeypairError::InvalidBase58 => write!(f, "invalid base58 key material"),
+            KeypairError::Io(ref e) => write!(f, "i/o error: {}", e),
+        }
+    }
+}
+
+impl Error for KeypairError {}
+
+impl From<::std::io::Error> for KeypairError {
+    fn from(e: ::std::io::Error) -> Self {
+        KeypairError::Io(e)
+    }
+}
+
+/// A long-term Ed25519 keypair: a 32-byte seed plus its derived public
+/// key, serializable to raw bytes or base58 and persisted to a file
+/// with restrictive permissions.
+pub struct Keypair {
+    seed: [u8; SEED_LEN],
+    public_key: Vec<u8>,
+}
+
+impl Keypair {
+    /// Generate a fresh keypair from the system CSPRNG.
+    pub fn generate() -> Self {
+        let mut seed = [0u8; SEED_LEN];
+        rand::thread_rng().fill_bytes(&mut seed);
+        Self::from_seed(&seed).expect("freshly generated seed is always valid")
+    }
+
+    /// Build a keypair from a 32-byte seed, deriving its public key.
+    /// Rejects seeds that are not exactly `SEED_LEN` bytes.
+    pub fn from_seed(seed: &[u8]) -> Result<Self, KeypairError> {
+        if seed.len() != SEED_LEN {
+            return Err(KeypairError::InvalidLength(seed.len()));
+        }
+
+        let mut seed_bytes = [0u8; SEED_LEN];
+        seed_bytes.copy_from_slice(seed);
+
+        let signer = Signer::<Ed25519Scheme>::new(&seed_bytes);
+        let public_key = signer.public_key_bytes().to_vec();
+
+        Ok(Keypair {
+            seed: seed_bytes,
+            public_key,
+        })
+    }
+
+    pub fn seed_bytes(&self) -> &[u8] {
+        &self.seed
+    }
+
+    pub fn public_key_bytes(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// A `Signer` over this keypair's long-term key.
+    pub fn signer(&self) -> Signer<Ed25519Scheme> {
+        Signer::<Ed25519Scheme>::new(&self.seed)
+    }
+
+    /// Base58-encode the raw seed, for compact human-copyable storage.
+    pub fn to_base58_string(&self) -> String {
+        bs58::encode(&self.seed[..]).into_string()
+    }
+
+    /// Decode a base58-encoded seed produced by `to_base58_string`.
+    pub fn from_base58_string(s: &str) -> Result<Self, KeypairError> {
+        let bytes = bs58::decode(s).into_vec().map_err(|_| KeypairError::InvalidBase58)?;
+        Self::from_seed(&bytes)
+    }
+
+    /// Write the raw seed to `path`, restricting permissions to the
+    /// owner (mode `0600` on unix) so the long-term key isn't left
+    /// world-readable on disk. The restrictive mode is applied atomically
+    /// at file creation rather than after the fact, so the seed is never
+    /// briefly readable under the umask's default permissions.
+    #[cfg(unix)]
+    pub fn write_keypair_file<P: AsRef<Path>>(&self, path: P) -> Result<(), KeypairError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        // `mode` only applies to a newly created file; tighten an existing
+        // one before the seed is written into it.
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(&self.seed)?;
+        Ok(())
+    }
+
+    /// Write the raw seed to `path`. Non-unix platforms have no
+    /// equivalent of a unix file mode, so permissions are left at the
+    /// platform default.
+    #[cfg(not(unix))]
+    pub fn write_keypair_file<P: AsRef<Path>>(&self, path: P) -> Result<(), KeypairError> {
+        let mut file = File::create(&path)?;
+        file.write_all(&self.seed)?;
+        Ok(())
+    }
+
+    /// Read a raw seed previously written by `write_keypair_file`,
+    /// rejecting files that are malformed or truncated.
+    pub fn read_keypair_file<P: AsRef<Path>>(path: P) -> Result<Self, KeypairError> {
+        let mut file = File::open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        Self::from_seed(&bytes)
+    }
+}
+
+impl Signer<Ed25519Scheme> {
+    /// Load a long-term key previously saved with
+    /// `Keypair::write_keypair_file`, so deployment tooling can load
+    /// keys without hand-rolling hex.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, KeypairError> {
+        Ok(Keypair::read_keypair_file(path)?.signer())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn generated_keypair_signs_and_verifies() {
+        let keypair = Keypair::generate();
+        let mut signer = keypair.signer();
+        signer.update(b"Hello world");
+        let sig = signer.sign();
+
+        let mut v = ::sign::Verifier::<Ed25519Scheme>::new(keypair.public_key_bytes());
+        v.update(b"Hello world");
+        assert_eq!(v.verify(&sig), true);
+    }
+
+    #[test]
+    fn base58_round_trip_preserves_seed() {
+        let keypair = Keypair::generate();
+        let encoded = keypair.to_base58_string();
+        let decoded = Keypair::from_base58_string(&encoded).unwrap();
+
+        assert_eq!(decoded.seed_bytes(), keypair.seed_bytes());
+        assert_eq!(decoded.public_key_bytes(), keypair.public_key_bytes());
+    }
+
+    #[test]
+    fn from_seed_rejects_truncated_material() {
+        let result = Keypair::from_seed(&[1u8; 16]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn from_base58_string_rejects_malformed_input() {
+        let result = Keypair::from_base58_string("not valid base58!!");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn write_and_read_keypair_file_round_trips() {
+        let path = ::std::env::temp_dir().join(format!("roughenough-test-keypair-{}.key", ::std::process::id()));
+
+        let keypair = Keypair::generate();
+        keypair.write_keypair_file(&path).unwrap();
+
+        let loaded = Keypair::read_keypair_file(&path).unwrap();
+        assert_eq!(loaded.seed_bytes(), keypair.seed_bytes());
+
+        let signer = Signer::<Ed25519Scheme>::from_file(&path).unwrap();
+        assert_eq!(signer.public_key_bytes(), keypair.public_key_bytes());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_keypair_file_restricts_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = ::std::env::temp_dir().join(format!("roughenough-test-keypair-perms-{}.key", ::std::process::id()));
+
+        let keypair = Keypair::generate();
+        keypair.write_keypair_file(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn read_keypair_file_rejects_truncated_file() {
+        let path = ::std::env::temp_dir().join(format!("roughenough-test-keypair-bad-{}.key", ::std::process::id()));
+        fs::write(&path, &[1u8; 4]).unwrap();
+
+        let result = Keypair::read_keypair_file(&path);
+        assert!(result.is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -12,32 +12,126 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Ed25519 signing and verification
+//! Pluggable signing and verification
 //!
 //! `Ring` does not provide a multi-step (init-update-finish) interface
-//! for Ed25519 signatures. `Verifier` and `Signer` provide this 
-//! missing multi-step api.
+//! for Ed25519 signatures. `Verifier` and `Signer` provide this
+//! missing multi-step api, generic over any `SignatureScheme` so a
+//! server can run with an Ed25519 long-term key (the default) or a
+//! different algorithm, such as the secp256k1 scheme in `bip340`.
 
+extern crate curve25519_dalek;
+extern crate rand;
 extern crate ring;
+extern crate sha2;
 extern crate untrusted;
 
+use self::curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
+use self::curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use self::curve25519_dalek::scalar::Scalar;
+use self::curve25519_dalek::traits::{IsIdentity, VartimeMultiscalarMul};
+use self::rand::RngCore;
 use self::ring::signature;
 use self::ring::signature::Ed25519KeyPair;
+use self::sha2::{Digest, Sha512};
 
 use self::untrusted::Input;
 
-/// A multi-step (init-update-finish) interface for verifying an 
-/// Ed25519 signature
-#[derive(Debug)]
-pub struct Verifier<'a> {
-    pubkey: Input<'a>,
+/// A pluggable signing algorithm.
+///
+/// `Signer` and `Verifier` wrap an implementation with the multi-step
+/// (init-update-finish) buffering the rest of the crate expects, so
+/// swapping the long-term key's algorithm doesn't touch call sites.
+/// `SCHEME_ID` tags which algorithm produced a signature, so a
+/// Roughtime message can record or negotiate it.
+pub trait SignatureScheme: Sized {
+    /// This scheme's public-key representation, as produced by
+    /// `decode_public_key` and stored by `Verifier` for the lifetime of
+    /// the verifier.
+    type PublicKey;
+
+    /// Identifier for this scheme, stable across releases so it can be
+    /// embedded in wire messages.
+    const SCHEME_ID: u32;
+
+    /// Domain-separation tag prepended to every message this scheme
+    /// signs or verifies, so signatures made under one scheme (or for
+    /// one purpose) can never be confused for another's. Defaults to
+    /// empty, which is what `Ed25519Scheme` uses to keep its signatures
+    /// identical to plain, context-free Ed25519.
+    fn context() -> &'static [u8] {
+        b""
+    }
+
+    /// Derive a keypair from a fixed-length seed.
+    fn from_seed(seed: &[u8]) -> Self;
+
+    /// Sign `msg` with this scheme's private key.
+    fn sign(&self, msg: &[u8]) -> Vec<u8>;
+
+    /// This scheme's public key, in its wire encoding.
+    fn public_key_bytes(&self) -> &[u8];
+
+    /// Decode a wire-format public key.
+    fn decode_public_key(bytes: &[u8]) -> Self::PublicKey;
+
+    /// Verify `sig` over `msg` under `pubkey`.
+    fn verify(pubkey: &Self::PublicKey, msg: &[u8], sig: &[u8]) -> bool;
+}
+
+/// The Ed25519 `SignatureScheme`, as used by the original Roughtime spec.
+/// This is the default scheme for `Signer`/`Verifier`.
+pub struct Ed25519Scheme {
+    key_pair: Ed25519KeyPair,
+}
+
+impl SignatureScheme for Ed25519Scheme {
+    type PublicKey = Vec<u8>;
+
+    const SCHEME_ID: u32 = 1;
+
+    fn from_seed(seed: &[u8]) -> Self {
+        Ed25519Scheme {
+            key_pair: Ed25519KeyPair::from_seed_unchecked(Input::from(seed)).unwrap(),
+        }
+    }
+
+    fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(msg).as_ref().to_vec()
+    }
+
+    fn public_key_bytes(&self) -> &[u8] {
+        self.key_pair.public_key_bytes()
+    }
+
+    fn decode_public_key(bytes: &[u8]) -> Self::PublicKey {
+        bytes.to_vec()
+    }
+
+    fn verify(pubkey: &Self::PublicKey, msg: &[u8], sig: &[u8]) -> bool {
+        let pubkey = Input::from(pubkey.as_slice());
+        let msg = Input::from(msg);
+        let sig = Input::from(sig);
+
+        match signature::verify(&signature::ED25519, pubkey, msg, sig) {
+            Ok(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A multi-step (init-update-finish) interface for verifying a
+/// signature under any `SignatureScheme`. Defaults to `Ed25519Scheme`
+/// so existing callers are unaffected.
+pub struct Verifier<S: SignatureScheme = Ed25519Scheme> {
+    pubkey: S::PublicKey,
     buf: Vec<u8>,
 }
 
-impl<'a> Verifier<'a> {
-    pub fn new(pubkey: &'a [u8]) -> Self {
+impl<S: SignatureScheme> Verifier<S> {
+    pub fn new(pubkey: &[u8]) -> Self {
         Verifier {
-            pubkey: Input::from(pubkey),
+            pubkey: S::decode_public_key(pubkey),
             buf: Vec::with_capacity(256),
         }
     }
@@ -48,27 +142,100 @@ impl<'a> Verifier<'a> {
     }
 
     pub fn verify(&self, expected_sig: &[u8]) -> bool {
-        let msg = Input::from(&self.buf);
-        let sig = Input::from(expected_sig);
+        let mut msg = S::context().to_vec();
+        msg.extend_from_slice(&self.buf);
 
-        match signature::verify(&signature::ED25519, self.pubkey, msg, sig) {
-            Ok(_) => true,
-            _ => false,
+        S::verify(&self.pubkey, &msg, expected_sig)
+    }
+
+    /// Identifier of the scheme this verifier checks signatures under.
+    pub fn scheme_id(&self) -> u32 {
+        S::SCHEME_ID
+    }
+}
+
+impl Verifier<Ed25519Scheme> {
+    /// Verify many Ed25519 signatures at once.
+    ///
+    /// `ring` has no batch-verification primitive, so this checks the
+    /// random-linear-combination equation directly against
+    /// `curve25519-dalek`: for each `(pubkey, message, signature)` tuple
+    /// split the signature into `R` (first 32 bytes) and `s` (last 32
+    /// bytes), compute `k = SHA512(R || pubkey || message) mod L`, draw a
+    /// fresh random 128-bit scalar `z`, and accept the whole batch iff
+    /// `[-sum(z*s)]*B + sum([z]*R) + sum([z*k]*A) == identity`. Returns
+    /// `false` if any public key or `R` fails to decode to a canonical
+    /// point, or if the batch is otherwise invalid.
+    pub fn verify_batch(items: &[(&[u8], &[u8], &[u8])]) -> bool {
+        if items.is_empty() {
+            return true;
         }
+
+        let mut rng = rand::thread_rng();
+        let mut scalars = Vec::with_capacity(1 + 2 * items.len());
+        let mut points = Vec::with_capacity(1 + 2 * items.len());
+        let mut s_sum = Scalar::zero();
+
+        for &(pubkey, message, sig) in items {
+            if sig.len() != 64 || pubkey.len() != 32 {
+                return false;
+            }
+
+            let r_bytes = &sig[..32];
+            let s_bytes = &sig[32..];
+
+            let r = match CompressedEdwardsY::from_slice(r_bytes).decompress() {
+                Some(point) if point.compress().as_bytes() == r_bytes => point,
+                _ => return false,
+            };
+            let a = match CompressedEdwardsY::from_slice(pubkey).decompress() {
+                Some(point) if point.compress().as_bytes() == pubkey => point,
+                _ => return false,
+            };
+
+            let mut s_canonical = [0u8; 32];
+            s_canonical.copy_from_slice(s_bytes);
+            let s = match Scalar::from_canonical_bytes(s_canonical) {
+                Some(s) => s,
+                None => return false,
+            };
+
+            let mut hasher = Sha512::new();
+            hasher.update(r_bytes);
+            hasher.update(pubkey);
+            hasher.update(message);
+            let k = Scalar::from_hash(hasher);
+
+            let mut z_bytes = [0u8; 16];
+            rng.fill_bytes(&mut z_bytes);
+            let z = Scalar::from(u128::from_le_bytes(z_bytes));
+
+            s_sum += z * s;
+            scalars.push(z);
+            points.push(r);
+            scalars.push(z * k);
+            points.push(a);
+        }
+
+        scalars.push(-s_sum);
+        points.push(ED25519_BASEPOINT_TABLE.basepoint());
+
+        EdwardsPoint::vartime_multiscalar_mul(&scalars, &points).is_identity()
     }
 }
 
-/// A multi-step (init-update-finish) interface for creating an 
-/// Ed25519 signature
-pub struct Signer {
-    key_pair: Ed25519KeyPair,
+/// A multi-step (init-update-finish) interface for creating a
+/// signature under any `SignatureScheme`. Defaults to `Ed25519Scheme`
+/// so existing callers are unaffected.
+pub struct Signer<S: SignatureScheme = Ed25519Scheme> {
+    scheme: S,
     buf: Vec<u8>,
 }
 
-impl Signer {
+impl<S: SignatureScheme> Signer<S> {
     pub fn new(seed: &[u8]) -> Self {
         Signer {
-            key_pair: Ed25519KeyPair::from_seed_unchecked(Input::from(seed)).unwrap(),
+            scheme: S::from_seed(seed),
             buf: Vec::with_capacity(256),
         }
     }
@@ -79,14 +246,22 @@ impl Signer {
     }
 
     pub fn sign(&mut self) -> Vec<u8> {
-        let signature = self.key_pair.sign(&self.buf).as_ref().to_vec();
+        let mut msg = S::context().to_vec();
+        msg.extend_from_slice(&self.buf);
+
+        let signature = self.scheme.sign(&msg);
         self.buf.clear();
 
         signature
     }
 
     pub fn public_key_bytes(&self) -> &[u8] {
-        self.key_pair.public_key_bytes()
+        self.scheme.public_key_bytes()
+    }
+
+    /// Identifier of the scheme producing this signer's signatures.
+    pub fn scheme_id(&self) -> u32 {
+        S::SCHEME_ID
     }
 }
 
@@ -105,7 +280,7 @@ mod test {
             .from_hex()
             .unwrap();
 
-        let v = Verifier::new(&pubkey);
+        let v = Verifier::<Ed25519Scheme>::new(&pubkey);
         let result = v.verify(&signature);
         assert_eq!(result, true);
     }
@@ -122,7 +297,7 @@ mod test {
             .from_hex()
             .unwrap();
 
-        let mut v = Verifier::new(&pubkey);
+        let mut v = Verifier::<Ed25519Scheme>::new(&pubkey);
         v.update(&message);
         let result = v.verify(&signature);
         assert_eq!(result, true);
@@ -138,7 +313,7 @@ mod test {
             .from_hex()
             .unwrap();
 
-        let mut s = Signer::new(&seed);
+        let mut s = Signer::<Ed25519Scheme>::new(&seed);
         let sig = s.sign();
         assert_eq!(sig, expected_sig);
     }
@@ -155,7 +330,7 @@ mod test {
             .from_hex()
             .unwrap();
 
-        let mut s = Signer::new(&seed);
+        let mut s = Signer::<Ed25519Scheme>::new(&seed);
         s.update(&message);
         let sig = s.sign();
         assert_eq!(sig, expected_sig);
@@ -169,15 +344,131 @@ mod test {
 
         let message = "Hello world".as_bytes();
 
-        let mut signer = Signer::new(&seed);
+        let mut signer = Signer::<Ed25519Scheme>::new(&seed);
         signer.update(&message);
         let signature = signer.sign();
 
-        let mut v = Verifier::new(signer.public_key_bytes());
+        let mut v = Verifier::<Ed25519Scheme>::new(signer.public_key_bytes());
         v.update(&message);
         let result = v.verify(&signature);
 
         assert_eq!(result, true);
     }
 
+    #[test]
+    fn verify_batch_accepts_multiple_valid_signatures() {
+        let seed_a = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            .from_hex()
+            .unwrap();
+        let seed_b = "0d4a05b07352a5436e180356da0ae6efa0345ff7fb1572575772e8005ed978e9"
+            .from_hex()
+            .unwrap();
+
+        let mut signer_a = Signer::<Ed25519Scheme>::new(&seed_a);
+        let sig_a = signer_a.sign();
+        let pubkey_a = signer_a.public_key_bytes().to_vec();
+
+        let message_b = "cbc77b".from_hex().unwrap();
+        let mut signer_b = Signer::<Ed25519Scheme>::new(&seed_b);
+        signer_b.update(&message_b);
+        let sig_b = signer_b.sign();
+        let pubkey_b = signer_b.public_key_bytes().to_vec();
+
+        let items: Vec<(&[u8], &[u8], &[u8])> = vec![
+            (&pubkey_a, &[], &sig_a),
+            (&pubkey_b, &message_b, &sig_b),
+        ];
+
+        assert_eq!(Verifier::verify_batch(&items), true);
+    }
+
+    #[test]
+    fn verify_batch_rejects_one_bad_signature() {
+        let seed_a = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            .from_hex()
+            .unwrap();
+        let seed_b = "0d4a05b07352a5436e180356da0ae6efa0345ff7fb1572575772e8005ed978e9"
+            .from_hex()
+            .unwrap();
+
+        let mut signer_a = Signer::<Ed25519Scheme>::new(&seed_a);
+        let sig_a = signer_a.sign();
+        let pubkey_a = signer_a.public_key_bytes().to_vec();
+
+        let message_b = "cbc77b".from_hex().unwrap();
+        let mut signer_b = Signer::<Ed25519Scheme>::new(&seed_b);
+        signer_b.update(&message_b);
+        let mut sig_b = signer_b.sign();
+        let pubkey_b = signer_b.public_key_bytes().to_vec();
+        sig_b[0] ^= 0xff;
+
+        let items: Vec<(&[u8], &[u8], &[u8])> = vec![
+            (&pubkey_a, &[], &sig_a),
+            (&pubkey_b, &message_b, &sig_b),
+        ];
+
+        assert_eq!(Verifier::verify_batch(&items), false);
+    }
+
+    #[test]
+    fn verify_batch_rejects_non_canonical_r() {
+        let seed_a = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            .from_hex()
+            .unwrap();
+
+        let mut signer_a = Signer::<Ed25519Scheme>::new(&seed_a);
+        let mut sig_a = signer_a.sign();
+        let pubkey_a = signer_a.public_key_bytes().to_vec();
+
+        // The field modulus p = 2^255 - 19, little-endian. Decompressing
+        // this y-coordinate succeeds (it reduces to y = 0, a valid
+        // point), but its canonical encoding is all-zero, not this
+        // value, so it must be rejected rather than silently accepted
+        // as an alternate encoding of the same point.
+        let non_canonical_r: [u8; 32] = [
+            0xed, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0x7f,
+        ];
+        sig_a[..32].copy_from_slice(&non_canonical_r);
+
+        let items: Vec<(&[u8], &[u8], &[u8])> = vec![(&pubkey_a, &[], &sig_a)];
+
+        assert_eq!(Verifier::verify_batch(&items), false);
+    }
+
+    #[test]
+    fn verify_batch_rejects_non_canonical_pubkey() {
+        let seed_a = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            .from_hex()
+            .unwrap();
+
+        let mut signer_a = Signer::<Ed25519Scheme>::new(&seed_a);
+        let sig_a = signer_a.sign();
+
+        // Same non-canonical-y trick as above, applied to the public
+        // key `A` instead of the signature's `R`.
+        let non_canonical_pubkey: [u8; 32] = [
+            0xed, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            0xff, 0xff, 0xff, 0x7f,
+        ];
+
+        let items: Vec<(&[u8], &[u8], &[u8])> = vec![(&non_canonical_pubkey, &[], &sig_a)];
+
+        assert_eq!(Verifier::verify_batch(&items), false);
+    }
+
+    #[test]
+    fn scheme_id_identifies_ed25519() {
+        let seed = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"
+            .from_hex()
+            .unwrap();
+
+        let signer = Signer::<Ed25519Scheme>::new(&seed);
+        assert_eq!(signer.scheme_id(), Ed25519Scheme::SCHEME_ID);
+
+        let verifier = Verifier::<Ed25519Scheme>::new(signer.public_key_bytes());
+        assert_eq!(verifier.scheme_id(), Ed25519Scheme::SCHEME_ID);
+    }
 }